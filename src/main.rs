@@ -1,5 +1,10 @@
 use std::{fs, env};
+use std::fs::File;
+use std::future::Future;
+use std::io::{Read, Seek, SeekFrom};
 use std::path::PathBuf;
+use std::pin::Pin;
+use std::task::{Context, Poll};
 use std::time::{Duration, Instant, UNIX_EPOCH};
 use home;
 use actix_files;
@@ -7,9 +12,11 @@ use actix_web::{
     get, web, App, error,
     HttpServer, Responder, Result,
     middleware::Logger,
-    HttpResponse,
-    http::{header::ContentType, StatusCode},
+    HttpRequest, HttpResponse,
+    web::Bytes,
+    http::{header, header::ContentType, StatusCode},
 };
+use futures_core::Stream;
 use dotenv::dotenv;
 use serde::Serialize;
 use serde::Deserialize;
@@ -84,7 +91,66 @@ struct PathQuery {
     path: String,
 }
 
-const EXTENSIONS: [&'static str; 9] = [
+#[derive(Deserialize)]
+struct ThumbQuery {
+    path: String,
+    size: Option<u32>,
+}
+
+#[derive(Deserialize)]
+struct FolderHashQuery {
+    path: String,
+    #[serde(default)]
+    include_content: bool,
+    #[serde(default)]
+    recursive: bool,
+}
+
+#[derive(Deserialize)]
+struct SimilarQuery {
+    path: String,
+    threshold: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct ExifInfo {
+    camera: Option<String>,
+    orientation: Option<u16>,
+    capture_date: Option<String>,
+    gps_latitude: Option<f64>,
+    gps_longitude: Option<f64>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ImageMetadata {
+    id: String,
+    path: String,
+    size: u64,
+    created: Option<f64>,
+    modified: f64,
+    mime: String,
+    hash: String,
+    width: Option<u32>,
+    height: Option<u32>,
+    exif: ExifInfo,
+    // Source mtime the record was computed against; used to invalidate the
+    // sidecar when the original changes. Not really consumer-facing.
+    source_mtime: f64,
+}
+
+#[derive(Serialize)]
+struct SimilarMember {
+    path: String,
+    distance: u32,
+}
+
+#[derive(Serialize)]
+struct SimilarGroup {
+    hash: String,
+    files: Vec<SimilarMember>,
+}
+
+const EXTENSIONS: &[&str] = &[
     ".jpg",
     ".jpeg",
     ".png",
@@ -94,8 +160,54 @@ const EXTENSIONS: [&'static str; 9] = [
     ".webm",
     ".mp4",
     ".mkv",
+    // RAW camera formats and HEIF, decoded on demand behind the `raw`/`heif`
+    // features. Listed unconditionally so the grid surfaces them either way.
+    ".cr2",
+    ".nef",
+    ".arw",
+    ".dng",
+    ".rw2",
+    ".orf",
+    ".raf",
+    ".heic",
+    ".heif",
 ];
 
+// The image formats of EXTENSIONS; the video formats are excluded here. SVG is
+// passed through as-is rather than decoded, and RAW/HEIF decode only when their
+// cargo feature is enabled.
+const IMAGE_EXTENSIONS: &[&str] = &[
+    ".jpg",
+    ".jpeg",
+    ".png",
+    ".gif",
+    ".webp",
+    ".svg",
+    ".cr2",
+    ".nef",
+    ".arw",
+    ".dng",
+    ".rw2",
+    ".orf",
+    ".raf",
+    ".heic",
+    ".heif",
+];
+
+const RAW_EXTENSIONS: &[&str] = &[
+    ".cr2",
+    ".nef",
+    ".arw",
+    ".dng",
+    ".rw2",
+    ".orf",
+    ".raf",
+];
+
+const HEIF_EXTENSIONS: &[&str] = &[".heic", ".heif"];
+
+const DEFAULT_THUMB_SIZE: u32 = 256;
+
 
 fn listdrives() -> Vec<String> {
     let mut drives = vec![];
@@ -123,9 +235,33 @@ fn default_path() -> PathBuf {
     }
 }
 
-fn calculate_folder_hash(path: PathBuf) -> Result<(String, Duration)> {
+// Cap recursion so a hash of a deep tree (or a symlink loop) can't run away.
+const MAX_HASH_DEPTH: usize = 8;
+
+// The folder hash is a cache-invalidation token for the front-end's polling.
+// In its cheapest form it is just a checksum of the sorted entry names, but
+// with `include_content` it also folds each file's mtime and size (so edits
+// and same-set renames are detected) and with `recursive` it descends into
+// subdirectories up to `MAX_HASH_DEPTH`. BLAKE3 keeps this fast on large trees.
+fn calculate_folder_hash(
+    path: PathBuf,
+    include_content: bool,
+    recursive: bool,
+) -> Result<(String, Duration)> {
     let start = Instant::now();
-    let mut names: Vec<String> = vec![];
+    let mut hasher = blake3::Hasher::new();
+    hash_dir(&path, include_content, recursive, 0, &mut hasher)?;
+    Ok((hasher.finalize().to_hex().to_string(), start.elapsed()))
+}
+
+fn hash_dir(
+    path: &PathBuf,
+    include_content: bool,
+    recursive: bool,
+    depth: usize,
+    hasher: &mut blake3::Hasher,
+) -> Result<()> {
+    let mut entries: Vec<(String, fs::Metadata, PathBuf)> = vec![];
 
     let readdir = fs::read_dir(path)?;
     for entry in readdir {
@@ -133,24 +269,38 @@ fn calculate_folder_hash(path: PathBuf) -> Result<(String, Duration)> {
             continue;
         };
 
-        let filename = direntry.file_name().to_owned();
+        let filename = direntry.file_name();
         let Some(strname) = filename.to_str() else {
             continue;
         };
-        names.push(strname.into());
+        let Ok(meta) = direntry.metadata() else {
+            continue;
+        };
+        entries.push((strname.into(), meta, direntry.path()));
     }
 
-    names.sort();
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
 
-    let mut hasher = Sha256::new();
-    for name in &names {
+    for (name, meta, entry_path) in &entries {
         hasher.update(name.as_bytes());
-    }
 
-    let result = hasher.finalize().to_vec();
-    let r2: Vec<_> = result.iter().map(|v| format!("{:02x}", v)).collect();
+        if include_content && meta.is_file() {
+            let mtime = meta
+                .modified()
+                .ok()
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .map(|d| d.as_nanos())
+                .unwrap_or(0);
+            hasher.update(&mtime.to_le_bytes());
+            hasher.update(&meta.len().to_le_bytes());
+        }
+
+        if recursive && meta.is_dir() && depth < MAX_HASH_DEPTH {
+            hash_dir(entry_path, include_content, recursive, depth + 1, hasher)?;
+        }
+    }
 
-    Ok((r2.join(""), start.elapsed()))
+    Ok(())
 }
 
 fn escape(s: String) -> String {
@@ -337,7 +487,7 @@ async fn get_folder_list(path: web::Query<PathQuery>) -> Result<impl Responder,
         }
     }
 
-    let Ok((hash, duration)) = calculate_folder_hash(input_path.clone()) else {
+    let Ok((hash, duration)) = calculate_folder_hash(input_path.clone(), false, false) else {
         warn!("Could not calculate folder hash.");
         return Err(HttpError::InternalServerError);
     };
@@ -351,8 +501,491 @@ async fn get_folder_list(path: web::Query<PathQuery>) -> Result<impl Responder,
     Ok(web::Json(folder_list))
 }
 
+// Cache thumbnails in a hidden sidecar directory next to the source, the same
+// way file-service keeps its `.thumbnails/<name>` previews. The cache key folds
+// in the source path, its mtime and byte size so a stale thumbnail is never
+// served after the original changes.
+fn thumbnail_cache_path(source: &PathBuf, size: u32) -> Result<PathBuf, HttpError> {
+    let meta = source.metadata().map_err(|_| HttpError::NotFound)?;
+    let mtime = meta
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_nanos())
+        .unwrap_or(0);
+
+    let parent = source.parent().ok_or(HttpError::BadRequest)?;
+    let name = source.file_name().ok_or(HttpError::BadRequest)?.to_string_lossy();
+
+    let mut hasher = Sha256::new();
+    hasher.update(source.to_string_lossy().as_bytes());
+    hasher.update(mtime.to_le_bytes());
+    hasher.update(meta.len().to_le_bytes());
+    hasher.update(size.to_le_bytes());
+    let key: String = hasher.finalize().iter().map(|v| format!("{:02x}", v)).collect();
+
+    Ok(parent.join(".thumbnails").join(format!("{}.{}.jpg", name, key)))
+}
+
+/// Decode an image from disk into a [`image::DynamicImage`], transparently
+/// handling RAW camera files and HEIF/HEIC when the matching cargo feature is
+/// enabled. Everything else goes through `image`'s own readers.
+fn load_dynamic_image(source: &PathBuf) -> Result<image::DynamicImage, HttpError> {
+    #[cfg(feature = "raw")]
+    {
+        let lowercase = source.to_string_lossy().to_ascii_lowercase();
+        if RAW_EXTENSIONS.iter().any(|v| lowercase.ends_with(v)) {
+            return decode_raw(source);
+        }
+    }
+
+    #[cfg(feature = "heif")]
+    {
+        let lowercase = source.to_string_lossy().to_ascii_lowercase();
+        if HEIF_EXTENSIONS.iter().any(|v| lowercase.ends_with(v)) {
+            return decode_heif(source);
+        }
+    }
+
+    // RAW/HEIF files are surfaced in listings unconditionally, but the decoders
+    // only exist when their feature is compiled in. Without it `image::open`
+    // can't read them, so report 404 rather than a misleading 500.
+    let lowercase = source.to_string_lossy().to_ascii_lowercase();
+    if RAW_EXTENSIONS.iter().chain(HEIF_EXTENSIONS).any(|v| lowercase.ends_with(v)) {
+        warn!("No decoder built in for {}", source.display());
+        return Err(HttpError::NotFound);
+    }
+
+    image::open(source).map_err(|e| {
+        warn!("Could not decode image {}: {}", source.display(), e);
+        HttpError::InternalServerError
+    })
+}
+
+// RAW: rawloader produces the sensor data, imagepipe develops it into an 8-bit
+// sRGB buffer which we wrap in an `ImageBuffer`.
+#[cfg(feature = "raw")]
+fn decode_raw(source: &PathBuf) -> Result<image::DynamicImage, HttpError> {
+    let developed = (|| -> std::result::Result<image::DynamicImage, Box<dyn std::error::Error>> {
+        let raw = rawloader::decode_file(source)?;
+        let source = imagepipe::ImageSource::Raw(raw);
+        let mut pipeline = imagepipe::Pipeline::new_from_source(source)?;
+        let image = pipeline.output_8bit(None)?;
+        let buf = image::ImageBuffer::from_raw(
+            image.width as u32,
+            image.height as u32,
+            image.data,
+        )
+        .ok_or("developed RAW buffer did not match its dimensions")?;
+        Ok(image::DynamicImage::ImageRgb8(buf))
+    })();
+
+    developed.map_err(|e| {
+        warn!("Could not decode RAW file {}: {}", source.display(), e);
+        HttpError::InternalServerError
+    })
+}
+
+// HEIF: libheif gives us the primary image as an interleaved RGB plane, which
+// we copy row by row (honouring the stride padding) into an `ImageBuffer`.
+#[cfg(feature = "heif")]
+fn decode_heif(source: &PathBuf) -> Result<image::DynamicImage, HttpError> {
+    use libheif_rs::{ColorSpace, HeifContext, LibHeif, RgbChroma};
+
+    let decoded = (|| -> std::result::Result<image::DynamicImage, Box<dyn std::error::Error>> {
+        let lib = LibHeif::new();
+        let path = source.to_str().ok_or("path is not valid UTF-8")?;
+        let ctx = HeifContext::read_from_file(path)?;
+        let handle = ctx.primary_image_handle()?;
+        let image = lib.decode(&handle, ColorSpace::Rgb(RgbChroma::Rgb), None)?;
+
+        let planes = image.planes();
+        let plane = planes.interleaved.ok_or("HEIF image has no interleaved plane")?;
+        let width = plane.width;
+        let height = plane.height;
+
+        let mut data = Vec::with_capacity((width as usize) * (height as usize) * 3);
+        for y in 0..height as usize {
+            let start = y * plane.stride;
+            data.extend_from_slice(&plane.data[start..start + (width as usize) * 3]);
+        }
+
+        let buf = image::ImageBuffer::from_raw(width, height, data)
+            .ok_or("decoded HEIF buffer did not match its dimensions")?;
+        Ok(image::DynamicImage::ImageRgb8(buf))
+    })();
+
+    decoded.map_err(|e| {
+        warn!("Could not decode HEIF file {}: {}", source.display(), e);
+        HttpError::InternalServerError
+    })
+}
+
+// Re-orient a decoded image according to its EXIF orientation tag so phone
+// photos shot in portrait don't come out sideways.
+fn apply_orientation(img: image::DynamicImage, orientation: u16) -> image::DynamicImage {
+    match orientation {
+        2 => img.fliph(),
+        3 => img.rotate180(),
+        4 => img.flipv(),
+        5 => img.rotate90().fliph(),
+        6 => img.rotate90(),
+        7 => img.rotate270().fliph(),
+        8 => img.rotate270(),
+        _ => img,
+    }
+}
+
+fn generate_thumbnail(source: &PathBuf, size: u32) -> Result<Vec<u8>, HttpError> {
+    let mut img = load_dynamic_image(source)?;
+
+    if let Some(orientation) = read_exif(source).orientation {
+        img = apply_orientation(img, orientation);
+    }
+
+    // `thumbnail` preserves aspect ratio, fitting inside a size x size box.
+    let thumb = image::DynamicImage::ImageRgb8(img.thumbnail(size, size).to_rgb8());
+
+    let mut buf = std::io::Cursor::new(Vec::new());
+    thumb.write_to(&mut buf, image::ImageFormat::Jpeg).map_err(|e| {
+        warn!("Could not encode thumbnail for {}: {}", source.display(), e);
+        HttpError::InternalServerError
+    })?;
+    Ok(buf.into_inner())
+}
+
+#[get("/thumbnail")]
+async fn get_thumbnail(query: web::Query<ThumbQuery>) -> Result<HttpResponse, HttpError> {
+    let (_, canonical_path) = normalize_path(PathBuf::from(&query.path));
+    let Ok(meta) = canonical_path.metadata() else {
+        return Err(HttpError::NotFound);
+    };
+    if !meta.is_file() {
+        return Err(HttpError::BadRequest);
+    }
+
+    let lowercase: String = canonical_path.to_string_lossy().to_ascii_lowercase();
+    if IMAGE_EXTENSIONS.iter().all(|v| !lowercase.ends_with(v)) {
+        return Err(HttpError::NotFound);
+    }
+
+    // SVG is resolution-independent, so there is nothing to downscale; hand the
+    // original back to the browser and let it render at whatever size it needs.
+    if lowercase.ends_with(".svg") {
+        let Ok(bytes) = fs::read(&canonical_path) else {
+            return Err(HttpError::InternalServerError);
+        };
+        return Ok(HttpResponse::Ok()
+            .content_type("image/svg+xml")
+            .body(bytes));
+    }
+
+    let size = query.size.unwrap_or(DEFAULT_THUMB_SIZE).clamp(16, 1024);
+    let cache_path = thumbnail_cache_path(&canonical_path, size)?;
+
+    if let Ok(cached) = fs::read(&cache_path) {
+        return Ok(HttpResponse::Ok().content_type("image/jpeg").body(cached));
+    }
+
+    let bytes = generate_thumbnail(&canonical_path, size)?;
+
+    if let Some(parent) = cache_path.parent() {
+        if let Err(e) = fs::create_dir_all(parent) {
+            warn!("Could not create thumbnail cache dir: {}", e);
+        }
+    }
+    if let Err(e) = fs::write(&cache_path, &bytes) {
+        warn!("Could not write thumbnail cache {}: {}", cache_path.display(), e);
+    }
+
+    Ok(HttpResponse::Ok().content_type("image/jpeg").body(bytes))
+}
+
+// dHash: shrink to 9x8 grayscale and emit one bit per horizontally-adjacent
+// pixel pair, giving a 64-bit fingerprint that survives rescaling and light
+// re-encoding.
+fn dhash(source: &PathBuf) -> Result<u64, HttpError> {
+    let img = load_dynamic_image(source)?;
+    let small = img
+        .resize_exact(9, 8, image::imageops::FilterType::Triangle)
+        .to_luma8();
+
+    let mut hash: u64 = 0;
+    let mut bit = 0;
+    for y in 0..8u32 {
+        for x in 0..8u32 {
+            let left = small.get_pixel(x, y)[0];
+            let right = small.get_pixel(x + 1, y)[0];
+            if left < right {
+                hash |= 1 << bit;
+            }
+            bit += 1;
+        }
+    }
+    Ok(hash)
+}
+
+// The fingerprint is cached in a `.phash/<name>` sidecar keyed on path + mtime,
+// so a repeat scan only re-decodes files that actually changed.
+fn phash_cache_path(source: &PathBuf) -> Result<PathBuf, HttpError> {
+    let meta = source.metadata().map_err(|_| HttpError::NotFound)?;
+    let mtime = meta
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_nanos())
+        .unwrap_or(0);
+
+    let parent = source.parent().ok_or(HttpError::BadRequest)?;
+    let name = source.file_name().ok_or(HttpError::BadRequest)?.to_string_lossy();
+
+    let mut hasher = Sha256::new();
+    hasher.update(source.to_string_lossy().as_bytes());
+    hasher.update(mtime.to_le_bytes());
+    let key: String = hasher.finalize().iter().map(|v| format!("{:02x}", v)).collect();
+
+    Ok(parent.join(".phash").join(format!("{}.{}.hash", name, key)))
+}
+
+fn cached_dhash(source: &PathBuf) -> Result<u64, HttpError> {
+    let cache_path = phash_cache_path(source)?;
+
+    if let Ok(text) = fs::read_to_string(&cache_path) {
+        if let Ok(hash) = u64::from_str_radix(text.trim(), 16) {
+            return Ok(hash);
+        }
+    }
+
+    let hash = dhash(source)?;
+
+    if let Some(parent) = cache_path.parent() {
+        if let Err(e) = fs::create_dir_all(parent) {
+            warn!("Could not create phash cache dir: {}", e);
+        }
+    }
+    if let Err(e) = fs::write(&cache_path, format!("{:016x}", hash)) {
+        warn!("Could not write phash cache {}: {}", cache_path.display(), e);
+    }
+
+    Ok(hash)
+}
+
+#[get("/similar")]
+async fn get_similar(query: web::Query<SimilarQuery>) -> Result<impl Responder, HttpError> {
+    let (_, canonical_path) = normalize_path(PathBuf::from(&query.path));
+    let Ok(meta) = canonical_path.metadata() else {
+        return Err(HttpError::NotFound);
+    };
+    if !meta.is_dir() {
+        return Err(HttpError::BadRequest);
+    }
+
+    let threshold = query.threshold.unwrap_or(10);
+
+    let Ok(readdir) = fs::read_dir(&canonical_path) else {
+        return Err(HttpError::InternalServerError);
+    };
+
+    let mut entries: Vec<(String, u64)> = vec![];
+    for entry in readdir {
+        let Ok(direntry) = entry else {
+            continue;
+        };
+        let Ok(entry_meta) = direntry.metadata() else {
+            continue;
+        };
+        if !entry_meta.is_file() {
+            continue;
+        }
+
+        let path = direntry.path();
+        let lowercase: String = path.to_string_lossy().to_ascii_lowercase();
+        // SVG can't be rasterised by `image`, so it has no perceptual hash.
+        if lowercase.ends_with(".svg") || IMAGE_EXTENSIONS.iter().all(|v| !lowercase.ends_with(v)) {
+            continue;
+        }
+
+        match cached_dhash(&path) {
+            Ok(hash) => entries.push((normalize_path(path).0, hash)),
+            Err(_) => warn!("Could not hash {}", path.display()),
+        }
+    }
+
+    // Greedy clustering: each unassigned entry seeds a group and takes in every
+    // later unassigned entry within `threshold` of that seed (not of the other
+    // members); distances are measured against the seed.
+    let mut assigned = vec![false; entries.len()];
+    let mut groups: Vec<SimilarGroup> = vec![];
+    for i in 0..entries.len() {
+        if assigned[i] {
+            continue;
+        }
+        assigned[i] = true;
+        let mut files = vec![SimilarMember {
+            path: entries[i].0.clone(),
+            distance: 0,
+        }];
+
+        for j in (i + 1)..entries.len() {
+            if assigned[j] {
+                continue;
+            }
+            let distance = (entries[i].1 ^ entries[j].1).count_ones();
+            if distance <= threshold {
+                assigned[j] = true;
+                files.push(SimilarMember {
+                    path: entries[j].0.clone(),
+                    distance,
+                });
+            }
+        }
+
+        if files.len() > 1 {
+            groups.push(SimilarGroup {
+                hash: format!("{:016x}", entries[i].1),
+                files,
+            });
+        }
+    }
+
+    groups.sort_by(|a, b| b.files.len().cmp(&a.files.len()));
+    Ok(web::Json(groups))
+}
+
+// Chunk size read per poll; keeps memory flat regardless of file size.
+const VIDEO_CHUNK_SIZE: u64 = 65_536;
+
+// Streams a bounded byte range of a file, reading one capped chunk at a time on
+// the blocking threadpool. Modeled on actix-files' own `ChunkedReadFile` so a
+// multi-GB video can be seeked without buffering the whole thing in memory.
+struct ChunkedReadFile {
+    size: u64,
+    offset: u64,
+    state: ChunkedReadState,
+}
+
+type ChunkFuture =
+    Pin<Box<dyn Future<Output = std::result::Result<std::io::Result<(File, Bytes)>, actix_web::error::BlockingError>>>>;
+
+enum ChunkedReadState {
+    File(Option<File>),
+    Future(ChunkFuture),
+}
+
+impl Stream for ChunkedReadFile {
+    type Item = std::result::Result<Bytes, actix_web::Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        // `ChunkedReadFile` is `Unpin` (the only heap-pinned data is the boxed
+        // future), so we can take a plain mutable reference to the fields.
+        let this = self.get_mut();
+        match this.state {
+            ChunkedReadState::File(ref mut opt_file) => {
+                let size = this.size;
+                let offset = this.offset;
+                if size == 0 {
+                    return Poll::Ready(None);
+                }
+
+                let mut file = opt_file.take().expect("polled ChunkedReadFile after completion");
+                let max = std::cmp::min(size, VIDEO_CHUNK_SIZE) as usize;
+                let fut = web::block(move || {
+                    let mut buf = vec![0u8; max];
+                    file.seek(SeekFrom::Start(offset))?;
+                    let read = file.read(&mut buf)?;
+                    if read == 0 {
+                        return Err(std::io::Error::new(
+                            std::io::ErrorKind::UnexpectedEof,
+                            "unexpected eof while streaming file",
+                        ));
+                    }
+                    buf.truncate(read);
+                    Ok((file, Bytes::from(buf)))
+                });
+                this.state = ChunkedReadState::Future(Box::pin(fut));
+                Pin::new(this).poll_next(cx)
+            }
+            ChunkedReadState::Future(ref mut fut) => {
+                let (file, bytes) = match fut.as_mut().poll(cx) {
+                    Poll::Ready(Ok(Ok(val))) => val,
+                    Poll::Ready(Ok(Err(e))) => return Poll::Ready(Some(Err(e.into()))),
+                    Poll::Ready(Err(e)) => return Poll::Ready(Some(Err(e.into()))),
+                    Poll::Pending => return Poll::Pending,
+                };
+                this.offset += bytes.len() as u64;
+                this.size -= bytes.len() as u64;
+                this.state = ChunkedReadState::File(Some(file));
+                Poll::Ready(Some(Ok(bytes)))
+            }
+        }
+    }
+}
+
+// Parse the first range of a `Range: bytes=start-end` header. Suffix ranges fall
+// back to serving the whole file; of a multi-range request only the first range
+// is served.
+fn parse_range(header: &str) -> Option<(u64, Option<u64>)> {
+    let spec = header.trim().strip_prefix("bytes=")?;
+    let first = spec.split(',').next()?;
+    let (start, end) = first.split_once('-')?;
+    let start: u64 = start.trim().parse().ok()?;
+    let end = end.trim();
+    let end = if end.is_empty() {
+        None
+    } else {
+        Some(end.parse().ok()?)
+    };
+    Some((start, end))
+}
+
+fn serve_video(req: &HttpRequest, path: &PathBuf, total: u64) -> Result<HttpResponse, HttpError> {
+    let file = File::open(path).map_err(|_| HttpError::InternalServerError)?;
+    let content_type = mime_for(path);
+
+    let range = req
+        .headers()
+        .get(header::RANGE)
+        .and_then(|h| h.to_str().ok())
+        .and_then(parse_range);
+
+    if let Some((start, maybe_end)) = range {
+        let end = maybe_end.unwrap_or(total.saturating_sub(1)).min(total.saturating_sub(1));
+        if total == 0 || start > end {
+            // RFC 7233: an unsatisfiable range gets 416 with the total size so
+            // players can recalculate and retry, not a bare 400.
+            return Ok(HttpResponse::RangeNotSatisfiable()
+                .insert_header((header::ACCEPT_RANGES, "bytes"))
+                .insert_header((header::CONTENT_RANGE, format!("bytes */{}", total)))
+                .finish());
+        }
+        let length = end - start + 1;
+        let reader = ChunkedReadFile {
+            size: length,
+            offset: start,
+            state: ChunkedReadState::File(Some(file)),
+        };
+        Ok(HttpResponse::PartialContent()
+            .insert_header((header::ACCEPT_RANGES, "bytes"))
+            .insert_header((header::CONTENT_TYPE, content_type))
+            .insert_header((header::CONTENT_RANGE, format!("bytes {}-{}/{}", start, end, total)))
+            .no_chunking(length)
+            .streaming(reader))
+    } else {
+        let reader = ChunkedReadFile {
+            size: total,
+            offset: 0,
+            state: ChunkedReadState::File(Some(file)),
+        };
+        Ok(HttpResponse::Ok()
+            .insert_header((header::ACCEPT_RANGES, "bytes"))
+            .insert_header((header::CONTENT_TYPE, content_type))
+            .no_chunking(total)
+            .streaming(reader))
+    }
+}
+
 #[get("/get-file")]
-async fn get_file(path: web::Query<PathQuery>) -> Result<actix_files::NamedFile, HttpError> {
+async fn get_file(req: HttpRequest, path: web::Query<PathQuery>) -> Result<HttpResponse, HttpError> {
     let (_, canonical_path) = normalize_path(PathBuf::from(&path.path));
     if !canonical_path.exists() {
         return Err(HttpError::NotFound);
@@ -363,15 +996,217 @@ async fn get_file(path: web::Query<PathQuery>) -> Result<actix_files::NamedFile,
     if !meta.is_file() {
         return Err(HttpError::BadRequest);
     }
+
+    let lowercase: String = canonical_path.to_string_lossy().to_ascii_lowercase();
+
+    // RAW and HEIF aren't browser-displayable, so develop them into a PNG on the
+    // fly instead of handing back the raw bytes.
+    if RAW_EXTENSIONS.iter().chain(HEIF_EXTENSIONS).any(|v| lowercase.ends_with(v)) {
+        let img = load_dynamic_image(&canonical_path)?;
+        let mut buf = std::io::Cursor::new(Vec::new());
+        img.write_to(&mut buf, image::ImageFormat::Png).map_err(|e| {
+            warn!("Could not encode {} to PNG: {}", canonical_path.display(), e);
+            HttpError::InternalServerError
+        })?;
+        return Ok(HttpResponse::Ok().content_type("image/png").body(buf.into_inner()));
+    }
+
+    // Large videos stream with proper Range/206 support so the browser can seek.
+    if lowercase.ends_with(".mp4") || lowercase.ends_with(".mkv") || lowercase.ends_with(".webm") {
+        return serve_video(&req, &canonical_path, meta.len());
+    }
+
     let Ok(file) = actix_files::NamedFile::open(canonical_path) else {
         return Err(HttpError::InternalServerError);
     };
-    Ok(file)
+    // NamedFile's own responder already negotiates Range requests for images.
+    Ok(file.into_response(&req))
 }
 
-#[get("/folder-hash")]
-async fn get_folder_hash(path: web::Query<PathQuery>) -> Result<impl Responder, HttpError> {
+fn systime_secs(time: std::io::Result<std::time::SystemTime>) -> f64 {
+    time.ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_secs_f64())
+        .unwrap_or(0.0)
+}
+
+fn mime_for(path: &PathBuf) -> String {
+    let lc = path.to_string_lossy().to_ascii_lowercase();
+    let mime = if lc.ends_with(".jpg") || lc.ends_with(".jpeg") {
+        "image/jpeg"
+    } else if lc.ends_with(".png") {
+        "image/png"
+    } else if lc.ends_with(".gif") {
+        "image/gif"
+    } else if lc.ends_with(".webp") {
+        "image/webp"
+    } else if lc.ends_with(".svg") {
+        "image/svg+xml"
+    } else if lc.ends_with(".heic") || lc.ends_with(".heif") {
+        "image/heif"
+    } else if lc.ends_with(".mp4") {
+        "video/mp4"
+    } else if lc.ends_with(".webm") {
+        "video/webm"
+    } else if lc.ends_with(".mkv") {
+        "video/x-matroska"
+    } else if RAW_EXTENSIONS.iter().any(|v| lc.ends_with(v)) {
+        "image/x-dcraw"
+    } else {
+        "application/octet-stream"
+    };
+    mime.into()
+}
+
+// Pull the handful of EXIF tags the UI cares about. Best-effort: a missing or
+// unparsable block just yields an empty record rather than an error.
+fn read_exif(path: &PathBuf) -> ExifInfo {
+    let mut info = ExifInfo::default();
+
+    let Ok(file) = fs::File::open(path) else {
+        return info;
+    };
+    let mut reader = std::io::BufReader::new(&file);
+    let Ok(exif) = exif::Reader::new().read_from_container(&mut reader) else {
+        return info;
+    };
+
+    if let Some(field) = exif.get_field(exif::Tag::Model, exif::In::PRIMARY) {
+        info.camera = Some(field.display_value().with_unit(&exif).to_string());
+    }
+    if let Some(field) = exif.get_field(exif::Tag::Orientation, exif::In::PRIMARY) {
+        info.orientation = field.value.get_uint(0).map(|v| v as u16);
+    }
+    if let Some(field) = exif.get_field(exif::Tag::DateTimeOriginal, exif::In::PRIMARY) {
+        info.capture_date = Some(field.display_value().to_string());
+    }
+    info.gps_latitude = gps_coord(&exif, exif::Tag::GPSLatitude, exif::Tag::GPSLatitudeRef);
+    info.gps_longitude = gps_coord(&exif, exif::Tag::GPSLongitude, exif::Tag::GPSLongitudeRef);
+
+    info
+}
+
+// GPS coordinates are stored as degrees/minutes/seconds rationals plus an N/S
+// or E/W reference; fold them into a signed decimal degree.
+fn gps_coord(exif: &exif::Exif, tag: exif::Tag, ref_tag: exif::Tag) -> Option<f64> {
+    let field = exif.get_field(tag, exif::In::PRIMARY)?;
+    let degrees = match &field.value {
+        exif::Value::Rational(parts) if parts.len() >= 3 => {
+            parts[0].to_f64() + parts[1].to_f64() / 60.0 + parts[2].to_f64() / 3600.0
+        }
+        _ => return None,
+    };
+
+    let sign = exif
+        .get_field(ref_tag, exif::In::PRIMARY)
+        .map(|f| f.display_value().to_string())
+        // ASCII values display wrapped in double quotes, e.g. `"S"`.
+        .map(|s| s.trim_start_matches('"').to_string())
+        .map(|s| if s.starts_with('S') || s.starts_with('W') { -1.0 } else { 1.0 })
+        .unwrap_or(1.0);
+
+    Some(degrees * sign)
+}
+
+fn metadata_cache_path(source: &PathBuf) -> Result<PathBuf, HttpError> {
+    let parent = source.parent().ok_or(HttpError::BadRequest)?;
+    let name = source.file_name().ok_or(HttpError::BadRequest)?.to_string_lossy();
+    Ok(parent.join(".metadata").join(format!("{}.json", name)))
+}
+
+fn compute_metadata(source: &PathBuf, meta: &fs::Metadata) -> Result<ImageMetadata, HttpError> {
+    let modified = systime_secs(meta.modified());
+    let created = meta
+        .created()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_secs_f64());
+
+    // Hash in bounded chunks rather than slurping the whole file into RAM, so a
+    // large source never balloons the worker's memory.
+    let mut file = File::open(source).map_err(|_| HttpError::InternalServerError)?;
+    let mut hasher = Sha256::new();
+    let mut buf = [0u8; 64 * 1024];
+    loop {
+        let read = file.read(&mut buf).map_err(|_| HttpError::InternalServerError)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buf[..read]);
+    }
+    let hash: String = hasher.finalize().iter().map(|v| format!("{:02x}", v)).collect();
+
+    let (width, height) = match image::image_dimensions(source) {
+        Ok((w, h)) => (Some(w), Some(h)),
+        Err(_) => (None, None),
+    };
+
+    let normalized = normalize_path(source.clone()).0;
+
+    Ok(ImageMetadata {
+        id: normalized.clone(),
+        path: normalized,
+        size: meta.len(),
+        created,
+        modified,
+        mime: mime_for(source),
+        hash,
+        width,
+        height,
+        exif: read_exif(source),
+        source_mtime: modified,
+    })
+}
+
+#[get("/metadata")]
+async fn get_metadata(path: web::Query<PathQuery>) -> Result<impl Responder, HttpError> {
     let (_, canonical_path) = normalize_path(PathBuf::from(&path.path));
+    let Ok(meta) = canonical_path.metadata() else {
+        return Err(HttpError::NotFound);
+    };
+    if !meta.is_file() {
+        return Err(HttpError::BadRequest);
+    }
+
+    let lowercase: String = canonical_path.to_string_lossy().to_ascii_lowercase();
+    if IMAGE_EXTENSIONS.iter().all(|v| !lowercase.ends_with(v)) {
+        return Err(HttpError::NotFound);
+    }
+
+    let modified = systime_secs(meta.modified());
+    let cache_path = metadata_cache_path(&canonical_path)?;
+
+    // Serve the sidecar as long as the source hasn't been touched since.
+    if let Ok(text) = fs::read_to_string(&cache_path) {
+        if let Ok(cached) = serde_json::from_str::<ImageMetadata>(&text) {
+            if (cached.source_mtime - modified).abs() < 1e-6 {
+                return Ok(web::Json(cached));
+            }
+        }
+    }
+
+    let record = compute_metadata(&canonical_path, &meta)?;
+
+    if let Some(parent) = cache_path.parent() {
+        if let Err(e) = fs::create_dir_all(parent) {
+            warn!("Could not create metadata cache dir: {}", e);
+        }
+    }
+    match serde_json::to_string_pretty(&record) {
+        Ok(json) => {
+            if let Err(e) = fs::write(&cache_path, json) {
+                warn!("Could not write metadata sidecar {}: {}", cache_path.display(), e);
+            }
+        }
+        Err(e) => warn!("Could not serialize metadata: {}", e),
+    }
+
+    Ok(web::Json(record))
+}
+
+#[get("/folder-hash")]
+async fn get_folder_hash(query: web::Query<FolderHashQuery>) -> Result<impl Responder, HttpError> {
+    let (_, canonical_path) = normalize_path(PathBuf::from(&query.path));
     if !canonical_path.exists() {
         return Err(HttpError::NotFound);
     }
@@ -381,7 +1216,7 @@ async fn get_folder_hash(path: web::Query<PathQuery>) -> Result<impl Responder,
     if !meta.is_dir() {
         return Err(HttpError::BadRequest);
     }
-    let Ok((hash, duration)) = calculate_folder_hash(canonical_path) else {
+    let Ok((hash, duration)) = calculate_folder_hash(canonical_path, query.include_content, query.recursive) else {
         warn!("Could not calculate folder hash.");
         return Err(HttpError::InternalServerError);
     };
@@ -401,6 +1236,9 @@ async fn main() -> std::io::Result<()> {
             .service(get_index)
             .service(get_folder_list)
             .service(get_file)
+            .service(get_thumbnail)
+            .service(get_similar)
+            .service(get_metadata)
             .service(get_folder_hash)
     })
         .bind(("127.0.0.1", 5000))?